@@ -0,0 +1,339 @@
+//! Helpers for systemd-style (`LISTEN_FDS`) socket activation.
+//!
+//! A supervisor such as systemd can bind sockets before exec'ing a service and
+//! hand the already-bound file descriptors down through the environment.
+//! `ListenFd` collects those descriptors on startup and lets the service claim
+//! each one as the concrete type it expects (`TcpListener`, `UnixListener`,
+//! ...) without having to fiddle with raw fds itself.
+
+use rustix::fd::OwnedFd;
+use std::fs::File;
+use std::io;
+use std::net::{TcpListener, UdpSocket};
+use std::os::unix::net::{UnixDatagram, UnixListener};
+
+mod unix;
+use unix as sys;
+pub use unix::VsockListener;
+
+/// The concrete type of an inherited descriptor, as determined by
+/// [`ListenFd::take_any`].
+pub enum FdKind {
+    TcpListener(TcpListener),
+    UdpSocket(UdpSocket),
+    UnixListener(UnixListener),
+    UnixDatagram(UnixDatagram),
+    Fifo(OwnedFd),
+    File(File),
+    Directory(OwnedFd),
+    MessageQueue(OwnedFd),
+}
+
+/// A descriptor type whose inherited value can be switched into non-blocking
+/// mode, for the benefit of [`ListenFd::nonblocking`].
+trait SetNonblocking {
+    fn set_nonblocking_flag(&self) -> io::Result<()>;
+}
+
+impl SetNonblocking for TcpListener {
+    fn set_nonblocking_flag(&self) -> io::Result<()> {
+        self.set_nonblocking(true)
+    }
+}
+
+impl SetNonblocking for UdpSocket {
+    fn set_nonblocking_flag(&self) -> io::Result<()> {
+        self.set_nonblocking(true)
+    }
+}
+
+impl SetNonblocking for UnixListener {
+    fn set_nonblocking_flag(&self) -> io::Result<()> {
+        self.set_nonblocking(true)
+    }
+}
+
+impl SetNonblocking for UnixDatagram {
+    fn set_nonblocking_flag(&self) -> io::Result<()> {
+        self.set_nonblocking(true)
+    }
+}
+
+impl SetNonblocking for OwnedFd {
+    fn set_nonblocking_flag(&self) -> io::Result<()> {
+        sys::set_nonblocking(self)
+    }
+}
+
+impl SetNonblocking for File {
+    // Regular files don't have a meaningful blocking mode; nothing to do.
+    fn set_nonblocking_flag(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SetNonblocking for FdKind {
+    fn set_nonblocking_flag(&self) -> io::Result<()> {
+        match self {
+            FdKind::TcpListener(v) => v.set_nonblocking_flag(),
+            FdKind::UdpSocket(v) => v.set_nonblocking_flag(),
+            FdKind::UnixListener(v) => v.set_nonblocking_flag(),
+            FdKind::UnixDatagram(v) => v.set_nonblocking_flag(),
+            FdKind::Fifo(v) | FdKind::Directory(v) | FdKind::MessageQueue(v) => {
+                v.set_nonblocking_flag()
+            }
+            FdKind::File(_) => Ok(()),
+        }
+    }
+}
+
+/// A single inherited descriptor, together with the name systemd assigned it
+/// (via `FileDescriptorName=`), if any.
+struct FdEntry {
+    name: Option<String>,
+    fd: Option<sys::FdType>,
+}
+
+/// The set of file descriptors inherited from the environment.
+///
+/// Descriptors are taken out one at a time with the `take_*` family of
+/// methods, either by their positional index or, if systemd named them, by
+/// name. Each descriptor can only be taken once; taking it again returns
+/// `Ok(None)`.
+pub struct ListenFd {
+    fds: Vec<FdEntry>,
+    nonblocking: bool,
+}
+
+impl ListenFd {
+    /// Collect the descriptors passed down via `LISTEN_FDS`/`LISTEN_FDNAMES`,
+    /// removing the corresponding environment variables so that child
+    /// processes don't also try to claim them.
+    pub fn from_env() -> ListenFd {
+        let fds = sys::get_fds()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, fd)| FdEntry {
+                name,
+                fd: Some(fd),
+            })
+            .collect();
+        ListenFd {
+            fds,
+            nonblocking: false,
+        }
+    }
+
+    /// Have subsequent `take_*` calls put the descriptor they return into
+    /// non-blocking mode, for use with async runtimes or poll-based loops.
+    /// Regular files (`take_file`, `FdKind::File`) are left unchanged.
+    /// Defaults to `false`, matching the fd's inherited blocking mode.
+    pub fn nonblocking(mut self, yes: bool) -> Self {
+        self.nonblocking = yes;
+        self
+    }
+
+    /// The number of descriptors that were inherited, whether or not they
+    /// have already been taken.
+    pub fn len(&self) -> usize {
+        self.fds.len()
+    }
+
+    /// Whether any descriptors were inherited.
+    pub fn is_empty(&self) -> bool {
+        self.fds.is_empty()
+    }
+
+    fn take_at<T>(
+        &mut self,
+        index: usize,
+        f: fn(sys::FdType) -> Result<T, (io::Error, sys::FdType)>,
+    ) -> io::Result<Option<T>> {
+        let entry = match self.fds.get_mut(index) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let fd = match entry.fd.take() {
+            Some(fd) => fd,
+            None => return Ok(None),
+        };
+        match f(fd) {
+            Ok(value) => Ok(Some(value)),
+            Err((err, fd)) => {
+                entry.fd = Some(fd);
+                Err(err)
+            }
+        }
+    }
+
+    fn take_by_name<T>(
+        &mut self,
+        name: &str,
+        f: fn(sys::FdType) -> Result<T, (io::Error, sys::FdType)>,
+    ) -> io::Result<Option<T>> {
+        match self.fds.iter().position(|entry| entry.name.as_deref() == Some(name)) {
+            Some(index) => self.take_at(index, f),
+            None => Ok(None),
+        }
+    }
+
+    /// If `self.nonblocking` is set, switch the taken descriptor into
+    /// non-blocking mode before handing it back.
+    fn apply_nonblocking<T: SetNonblocking>(
+        &self,
+        result: io::Result<Option<T>>,
+    ) -> io::Result<Option<T>> {
+        match result {
+            Ok(Some(value)) => {
+                if self.nonblocking {
+                    value.set_nonblocking_flag()?;
+                }
+                Ok(Some(value))
+            }
+            other => other,
+        }
+    }
+
+    /// Take the descriptor at `index` as a `TcpListener`, if it is one.
+    pub fn take_tcp_listener(&mut self, index: usize) -> io::Result<Option<TcpListener>> {
+        let result = self.take_at(index, sys::make_tcp_listener);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` (via `FileDescriptorName=`) as a
+    /// `TcpListener`, if it is one.
+    pub fn take_tcp_listener_by_name(&mut self, name: &str) -> io::Result<Option<TcpListener>> {
+        let result = self.take_by_name(name, sys::make_tcp_listener);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index` as a `UdpSocket`, if it is one.
+    pub fn take_udp_socket(&mut self, index: usize) -> io::Result<Option<UdpSocket>> {
+        let result = self.take_at(index, sys::make_udp_socket);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` (via `FileDescriptorName=`) as a
+    /// `UdpSocket`, if it is one.
+    pub fn take_udp_socket_by_name(&mut self, name: &str) -> io::Result<Option<UdpSocket>> {
+        let result = self.take_by_name(name, sys::make_udp_socket);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index` as a `UnixListener`, if it is one.
+    pub fn take_unix_listener(&mut self, index: usize) -> io::Result<Option<UnixListener>> {
+        let result = self.take_at(index, sys::make_unix_listener);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` (via `FileDescriptorName=`) as a
+    /// `UnixListener`, if it is one.
+    pub fn take_unix_listener_by_name(&mut self, name: &str) -> io::Result<Option<UnixListener>> {
+        let result = self.take_by_name(name, sys::make_unix_listener);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index` as a `UnixDatagram`, if it is one.
+    pub fn take_unix_datagram(&mut self, index: usize) -> io::Result<Option<UnixDatagram>> {
+        let result = self.take_at(index, sys::make_unix_datagram);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` (via `FileDescriptorName=`) as a
+    /// `UnixDatagram`, if it is one.
+    pub fn take_unix_datagram_by_name(&mut self, name: &str) -> io::Result<Option<UnixDatagram>> {
+        let result = self.take_by_name(name, sys::make_unix_datagram);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index`, inspecting it to determine its
+    /// concrete [`FdKind`] rather than requiring the caller to know it in
+    /// advance.
+    pub fn take_any(&mut self, index: usize) -> io::Result<Option<FdKind>> {
+        let result = self.take_at(index, sys::take_any);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` (via `FileDescriptorName=`),
+    /// inspecting it to determine its concrete [`FdKind`] rather than
+    /// requiring the caller to know it in advance.
+    pub fn take_any_by_name(&mut self, name: &str) -> io::Result<Option<FdKind>> {
+        let result = self.take_by_name(name, sys::take_any);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index` as a FIFO (from `ListenFIFO=`), if it
+    /// is one.
+    pub fn take_fifo(&mut self, index: usize) -> io::Result<Option<OwnedFd>> {
+        let result = self.take_at(index, sys::make_fifo);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` as a FIFO (from `ListenFIFO=`), if
+    /// it is one.
+    pub fn take_fifo_by_name(&mut self, name: &str) -> io::Result<Option<OwnedFd>> {
+        let result = self.take_by_name(name, sys::make_fifo);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index` as a `File` (from `ListenSpecial=`),
+    /// if it is a regular file.
+    pub fn take_file(&mut self, index: usize) -> io::Result<Option<File>> {
+        let result = self.take_at(index, sys::make_file);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` as a `File` (from `ListenSpecial=`),
+    /// if it is a regular file.
+    pub fn take_file_by_name(&mut self, name: &str) -> io::Result<Option<File>> {
+        let result = self.take_by_name(name, sys::make_file);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index` as a directory, if it is one.
+    pub fn take_directory(&mut self, index: usize) -> io::Result<Option<OwnedFd>> {
+        let result = self.take_at(index, sys::make_directory);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` as a directory, if it is one.
+    pub fn take_directory_by_name(&mut self, name: &str) -> io::Result<Option<OwnedFd>> {
+        let result = self.take_by_name(name, sys::make_directory);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index` as an `AF_VSOCK` listener, if it is
+    /// one. Used by services activated inside a VM that listen for
+    /// connections from the host (or vice versa).
+    pub fn take_vsock_listener(&mut self, index: usize) -> io::Result<Option<VsockListener>> {
+        let result = self.take_at(index, sys::make_vsock_listener);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` as an `AF_VSOCK` listener, if it is
+    /// one.
+    pub fn take_vsock_listener_by_name(&mut self, name: &str) -> io::Result<Option<VsockListener>> {
+        let result = self.take_by_name(name, sys::make_vsock_listener);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor at `index` as a POSIX message queue (from
+    /// `ListenMessageQueue=`), if it is one.
+    pub fn take_message_queue(&mut self, index: usize) -> io::Result<Option<OwnedFd>> {
+        let result = self.take_at(index, sys::make_message_queue);
+        self.apply_nonblocking(result)
+    }
+
+    /// Take the descriptor named `name` as a POSIX message queue (from
+    /// `ListenMessageQueue=`), if it is one.
+    pub fn take_message_queue_by_name(&mut self, name: &str) -> io::Result<Option<OwnedFd>> {
+        let result = self.take_by_name(name, sys::make_message_queue);
+        self.apply_nonblocking(result)
+    }
+}
+
+impl Default for ListenFd {
+    fn default() -> Self {
+        ListenFd::from_env()
+    }
+}
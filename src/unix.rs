@@ -113,7 +113,161 @@ pub fn make_custom<T: FromFd>(
         .map(|fd| FromFd::from_fd(fd))
 }
 
-pub fn get_fds() -> Option<Vec<FdType>> {
+/// An inherited `AF_VSOCK` listener, as the raw owned fd since `std` has no
+/// vsock type of its own.
+pub type VsockListener = OwnedFd;
+
+pub fn make_vsock_listener(fd: FdType) -> Result<VsockListener, (io::Error, FdType)> {
+    validate_socket(fd, AddressFamily::VSOCK, SocketType::STREAM, "vsock socket")
+        .and_then(mark_cloexec)
+}
+
+fn validate_file_type(
+    fd: FdType,
+    expected: FileType,
+    hint: &str,
+) -> Result<FdType, (io::Error, FdType)> {
+    let file_type = match rustix::fs::fstat(&fd) {
+        Ok(stat) => FileType::from_raw_mode(stat.st_mode),
+        Err(err) => return Err((err.into(), fd)),
+    };
+    if file_type != expected {
+        return Err((
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("fd {:?} is not a {}", fd, hint),
+            ),
+            fd,
+        ));
+    }
+    Ok(fd)
+}
+
+pub fn make_fifo(fd: FdType) -> Result<OwnedFd, (io::Error, FdType)> {
+    validate_file_type(fd, FileType::Fifo, "fifo").and_then(mark_cloexec)
+}
+
+pub fn make_file(fd: FdType) -> Result<std::fs::File, (io::Error, FdType)> {
+    validate_file_type(fd, FileType::RegularFile, "regular file")
+        .and_then(mark_cloexec)
+        .map(|fd| FromFd::from_fd(fd))
+}
+
+pub fn make_directory(fd: FdType) -> Result<OwnedFd, (io::Error, FdType)> {
+    validate_file_type(fd, FileType::Directory, "directory").and_then(mark_cloexec)
+}
+
+// From <linux/magic.h>.
+const MQUEUE_MAGIC: i64 = 0x19800202;
+
+fn is_message_queue(fd: &FdType) -> io::Result<bool> {
+    let statfs = rustix::fs::fstatfs(fd)?;
+    Ok(statfs.f_type as i64 == MQUEUE_MAGIC)
+}
+
+pub fn make_message_queue(fd: FdType) -> Result<OwnedFd, (io::Error, FdType)> {
+    match is_message_queue(&fd) {
+        Ok(true) => mark_cloexec(fd),
+        Ok(false) => Err((
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("fd {:?} is not a message queue", fd),
+            ),
+            fd,
+        )),
+        Err(err) => Err((err, fd)),
+    }
+}
+
+pub fn set_nonblocking(fd: &FdType) -> io::Result<()> {
+    let flags = rustix::fs::fcntl_getfl(fd)?;
+    rustix::fs::fcntl_setfl(fd, flags | rustix::fs::OFlags::NONBLOCK)?;
+    Ok(())
+}
+
+pub fn take_any(fd: FdType) -> Result<crate::FdKind, (io::Error, FdType)> {
+    let file_type = match rustix::fs::fstat(&fd) {
+        Ok(stat) => FileType::from_raw_mode(stat.st_mode),
+        Err(err) => return Err((err.into(), fd)),
+    };
+
+    match file_type {
+        FileType::Socket => take_any_socket(fd),
+        FileType::Fifo => make_fifo(fd).map(crate::FdKind::Fifo),
+        // A POSIX mqueue fd also reports `S_IFREG`, so it looks like a
+        // regular file to `fstat`; check for the mqueue magic first so it
+        // isn't silently handed back as `FdKind::File`.
+        FileType::RegularFile => match is_message_queue(&fd) {
+            Ok(true) => make_message_queue(fd).map(crate::FdKind::MessageQueue),
+            Ok(false) => make_file(fd).map(crate::FdKind::File),
+            Err(err) => Err((err, fd)),
+        },
+        FileType::Directory => make_directory(fd).map(crate::FdKind::Directory),
+        _ => Err((
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("fd {:?} is not a socket, fifo, file, or directory", fd),
+            ),
+            fd,
+        )),
+    }
+}
+
+fn take_any_socket(fd: FdType) -> Result<crate::FdKind, (io::Error, FdType)> {
+    let sock_type = rustix::net::sockopt::get_socket_type(&fd);
+    let family = rustix::net::getsockname(&fd).map(|addr| addr.address_family());
+    let known = |family: AddressFamily| {
+        family == AddressFamily::UNIX
+            || family == AddressFamily::INET
+            || family == AddressFamily::INET6
+    };
+
+    match (sock_type, family) {
+        (Ok(SocketType::STREAM), Ok(family)) if known(family) => {
+            if !rustix::net::sockopt::get_socket_acceptconn(&fd).unwrap_or(false) {
+                return Err((
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("fd {:?} is a stream socket but not listening", fd),
+                    ),
+                    fd,
+                ));
+            }
+            if family == AddressFamily::UNIX {
+                mark_cloexec(fd).map(|fd| crate::FdKind::UnixListener(FromFd::from_fd(fd)))
+            } else {
+                mark_cloexec(fd).map(|fd| crate::FdKind::TcpListener(FromFd::from_fd(fd)))
+            }
+        }
+        (Ok(SocketType::DGRAM), Ok(family)) if known(family) => {
+            if family == AddressFamily::UNIX {
+                mark_cloexec(fd).map(|fd| crate::FdKind::UnixDatagram(FromFd::from_fd(fd)))
+            } else {
+                mark_cloexec(fd).map(|fd| crate::FdKind::UdpSocket(FromFd::from_fd(fd)))
+            }
+        }
+        _ => Err((
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("fd {:?} is not a recognized socket kind", fd),
+            ),
+            fd,
+        )),
+    }
+}
+
+// Split `LISTEN_FDNAMES` (colon-separated, parallel to `LISTEN_FDS`) into one
+// name per descriptor, padding with `None` for any descriptor systemd didn't
+// name.
+fn parse_fd_names(raw: Option<String>, count: usize) -> Vec<Option<String>> {
+    let mut names: Vec<Option<String>> = raw
+        .map(|names| names.split(':').map(|name| Some(name.to_owned())).collect())
+        .unwrap_or_default();
+    names.resize(count, None);
+    names
+}
+
+pub fn get_fds() -> Option<Vec<(Option<String>, FdType)>> {
     // modified systemd protocol
     if let Some(count) = env::var("LISTEN_FDS").ok().and_then(|x| x.parse().ok()) {
         let ok = match env::var("LISTEN_PID").as_ref().map(|x| x.as_str()) {
@@ -122,12 +276,19 @@ pub fn get_fds() -> Option<Vec<FdType>> {
             _ => false,
         };
 
+        let names = parse_fd_names(env::var("LISTEN_FDNAMES").ok(), count);
+
         env::remove_var("LISTEN_PID");
         env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
         if ok {
             return Some(
-                (0..count)
-                    .map(|offset| unsafe { OwnedFd::from_raw_fd(3 + offset) })
+                names
+                    .into_iter()
+                    .enumerate()
+                    .map(|(offset, name)| {
+                        (name, unsafe { OwnedFd::from_raw_fd(3 + offset as i32) })
+                    })
                     .collect(),
             );
         }
@@ -135,3 +296,126 @@ pub fn get_fds() -> Option<Vec<FdType>> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fd_names_splits_on_colon() {
+        let names = parse_fd_names(Some("foo:bar:baz".to_owned()), 3);
+        assert_eq!(
+            names,
+            vec![
+                Some("foo".to_owned()),
+                Some("bar".to_owned()),
+                Some("baz".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_fd_names_pads_missing_names_with_none() {
+        let names = parse_fd_names(Some("foo".to_owned()), 3);
+        assert_eq!(names, vec![Some("foo".to_owned()), None, None]);
+    }
+
+    #[test]
+    fn parse_fd_names_absent_yields_all_none() {
+        let names = parse_fd_names(None, 2);
+        assert_eq!(names, vec![None, None]);
+    }
+
+    fn fd_kind_label(result: &Result<crate::FdKind, (io::Error, FdType)>) -> &'static str {
+        match result {
+            Ok(crate::FdKind::TcpListener(_)) => "TcpListener",
+            Ok(crate::FdKind::UdpSocket(_)) => "UdpSocket",
+            Ok(crate::FdKind::UnixListener(_)) => "UnixListener",
+            Ok(crate::FdKind::UnixDatagram(_)) => "UnixDatagram",
+            Ok(crate::FdKind::Fifo(_)) => "Fifo",
+            Ok(crate::FdKind::File(_)) => "File",
+            Ok(crate::FdKind::Directory(_)) => "Directory",
+            Ok(crate::FdKind::MessageQueue(_)) => "MessageQueue",
+            Err(_) => "Err",
+        }
+    }
+
+    #[test]
+    fn take_any_detects_listening_tcp_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let fd: FdType = listener.into();
+        let result = take_any(fd);
+        assert_eq!(fd_kind_label(&result), "TcpListener");
+    }
+
+    #[test]
+    fn take_any_rejects_connected_stream_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::net::TcpStream::connect(addr).unwrap();
+        let fd: FdType = client.into();
+        assert_eq!(fd_kind_label(&take_any(fd)), "Err");
+    }
+
+    #[test]
+    fn take_any_detects_fifo() {
+        let (read, _write) = rustix::pipe::pipe().unwrap();
+        assert_eq!(fd_kind_label(&take_any(read)), "Fifo");
+    }
+
+    #[test]
+    fn take_any_detects_directory() {
+        let dir = std::fs::File::open(".").unwrap();
+        let fd: FdType = dir.into();
+        assert_eq!(fd_kind_label(&take_any(fd)), "Directory");
+    }
+
+    #[test]
+    fn take_any_detects_regular_file() {
+        let fd = temp_file_fd("take-any");
+        assert_eq!(fd_kind_label(&take_any(fd)), "File");
+    }
+
+    fn temp_file_fd(tag: &str) -> FdType {
+        let mut path = std::env::temp_dir();
+        path.push(format!("listenfd-test-{}-{}", tag, std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        file.into()
+    }
+
+    #[test]
+    fn make_message_queue_rejects_regular_file() {
+        let fd = temp_file_fd("mqueue");
+        assert!(make_message_queue(fd).is_err());
+    }
+
+    #[test]
+    fn make_vsock_listener_rejects_non_vsock_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let fd: FdType = listener.into();
+        match make_vsock_listener(fd) {
+            Err((err, _fd)) => assert_eq!(err.kind(), io::ErrorKind::InvalidInput),
+            Ok(_) => panic!("expected a TCP listener to be rejected as a vsock socket"),
+        }
+    }
+
+    #[test]
+    fn nonblocking_toggle_applies_to_taken_tcp_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let fd: FdType = listener.into();
+        let mut listen_fd = crate::ListenFd {
+            fds: vec![crate::FdEntry {
+                name: None,
+                fd: Some(fd),
+            }],
+            nonblocking: false,
+        }
+        .nonblocking(true);
+
+        let taken = listen_fd.take_tcp_listener(0).unwrap().unwrap();
+
+        let flags = rustix::fs::fcntl_getfl(&taken).unwrap();
+        assert!(flags.contains(rustix::fs::OFlags::NONBLOCK));
+    }
+}